@@ -1,27 +1,122 @@
+use std::sync::{Arc, Mutex};
 use std::thread;
 use std::time::{Duration, Instant};
 
 const ZERO_TIME: Duration = Duration::from_secs(0);
 
-/// `Bucket` represents a token bucket that fills at a predetermined rate. Methods on
-/// `Bucket` may be called concurrently.
+/// rateMargin specifies the allowed slop between the requested rate and the
+/// realized rate of a bucket built with [`Bucket::with_rate`].
+const RATE_MARGIN: f64 = 0.01;
+
+/// The exclusive upper bound on the `quantum` values [`Bucket::with_rate`] will
+/// try before giving up on finding one whose realized rate is within
+/// [`RATE_MARGIN`].
+const MAX_QUANTUM_TRIES: u64 = 1 << 50;
+
+/// Available tokens are tracked internally as fixed-point fractions with a
+/// denominator of `TOKEN_MULTIPLIER`, so that sub-tick refills accumulate
+/// accurately instead of being truncated to whole tokens on every call. Refills
+/// are credited in steps of `1/TOKEN_MULTIPLIER` of a fill interval, so the
+/// credited tokens lag the exact amount by less than `quantum/TOKEN_MULTIPLIER`
+/// of a token; the lag does not accumulate across calls.
+const TOKEN_MULTIPLIER: u64 = 256;
+
+/// `Clock` abstracts the source of time used by a [`Bucket`]. The real
+/// implementation reads the monotonic clock, while [`ManualClock`] lets tests
+/// advance virtual time on demand instead of sleeping.
+pub trait Clock {
+    /// now returns the current instant according to this clock.
+    fn now(&self) -> Instant;
+}
+
+impl<C: Clock> Clock for Arc<C> {
+    fn now(&self) -> Instant {
+        (**self).now()
+    }
+}
+
+/// `RealClock` reads the operating system's monotonic clock.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct RealClock;
+
+impl Clock for RealClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// `ManualClock` is a [`Clock`] whose time only moves forward when
+/// [`ManualClock::advance`] is called, making time-dependent behaviour testable
+/// without `thread::sleep`.
 #[derive(Debug)]
-pub struct Bucket {
+pub struct ManualClock {
+    base: Instant,
+    offset: Mutex<Duration>,
+}
+
+impl ManualClock {
+    pub fn new() -> Self {
+        Self {
+            base: Instant::now(),
+            offset: Mutex::new(ZERO_TIME),
+        }
+    }
+
+    /// advance moves the clock forward by the given duration.
+    pub fn advance(&self, by: Duration) {
+        *self.offset.lock().unwrap() += by;
+    }
+}
+
+impl Default for ManualClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Instant {
+        self.base + *self.offset.lock().unwrap()
+    }
+}
+
+/// A rollback snapshot of a bucket's mutable accounting: its `available_tokens`
+/// and `latest_tick`, plus the same pair for its burst sub-bucket when present.
+type ReservationState = (i64, u64, Option<(i64, u64)>);
+
+/// `Bucket` represents a token bucket that fills at a predetermined rate. Its
+/// methods take `&mut self` and are not safe for concurrent use; use a
+/// [`SyncBucket`], which builds and owns its own bucket, to share one bucket
+/// across threads.
+#[derive(Debug)]
+pub struct Bucket<C: Clock = RealClock> {
     /// capacity holds the overall capacity of the bucket.
     pub capacity: u64,
-    /// availableTokens holds the number of available
-    /// tokens as of the associated latestTick.
-    /// It will be negative when there are consumers
-    /// waiting for tokens.
-    pub available_tokens: u64,
+    /// availableTokens holds the number of available tokens as of the associated
+    /// latestTick, measured in fixed-point fractions of `1/TOKEN_MULTIPLIER` of a
+    /// token rather than whole tokens. It goes negative when callers have reserved
+    /// tokens on the wait path that have not yet accrued.
+    pub available_tokens: i64,
     /// quantum holds how many tokens are added on
     /// each tick.
     pub quantum: u64,
     /// fillInterval holds the interval between each tick.
     pub fill_interval: Duration,
-    /// latestTick holds the latest tick for which
-    /// we know the number of tokens in the bucket.
-    pub latest_tick: Instant,
+    /// startTime holds the moment the bucket was created; all
+    /// ticks are measured as integer multiples of
+    /// fillInterval / TOKEN_MULTIPLIER elapsed since this instant.
+    pub start_time: Instant,
+    /// latestTick holds the latest tick for which we know the number of tokens in
+    /// the bucket, expressed in fractional ticks of `1/TOKEN_MULTIPLIER` so that
+    /// refills are credited at sub-tick resolution.
+    pub latest_tick: u64,
+    /// clock is the source of time the bucket reads; injectable so that
+    /// time-dependent logic can be tested deterministically.
+    pub clock: C,
+    /// burst, when present, is an auxiliary smaller bucket that caps how many
+    /// tokens may be dispensed at once even when the main bucket has capacity
+    /// to spare, smoothing out spikes.
+    pub burst: Option<Box<Bucket<C>>>,
 }
 
 impl Bucket {
@@ -31,29 +126,117 @@ impl Bucket {
         quantum: u64,
         available_tokens: u64,
     ) -> Self {
+        Self::with_clock(fill_interval, capacity, quantum, available_tokens, RealClock)
+    }
+
+    /// with_rate returns a new bucket that fills at the rate of `rate` tokens per
+    /// second, up to the given capacity. Because the actual rate is limited by the
+    /// resolution of the clock, `quantum` is searched upward from 1 until the
+    /// realized rate lands within [`RATE_MARGIN`] of the requested rate, allowing
+    /// rates far higher than one token per tick to be represented.
+    pub fn with_rate(rate: f64, capacity: u64) -> Result<Self, String> {
+        if !rate.is_finite() {
+            return Err(format!("rate must be finite, got {}", rate));
+        }
+        if rate <= 0.0 {
+            return Err(format!("rate must be positive, got {}", rate));
+        }
+        let mut quantum: u64 = 1;
+        while quantum < MAX_QUANTUM_TRIES {
+            let fill_interval = Duration::from_nanos((1e9 * quantum as f64 / rate) as u64);
+            if !fill_interval.is_zero() {
+                let actual_rate = quantum as f64 / fill_interval.as_secs_f64();
+                if (actual_rate - rate).abs() <= rate * RATE_MARGIN {
+                    return Ok(Self::new(fill_interval, capacity, quantum, capacity));
+                }
+            }
+            // Grow the quantum geometrically so rates requiring a large quantum are
+            // reached in O(log) steps rather than counting up one at a time.
+            quantum = (quantum + quantum / 4).max(quantum + 1);
+        }
+        Err(format!("cannot find suitable quantum for rate {}", rate))
+    }
+}
+
+impl<C: Clock> Bucket<C> {
+    /// with_clock is like [`Bucket::new`] but reads time through the supplied
+    /// [`Clock`] instead of the real monotonic clock.
+    pub fn with_clock(
+        fill_interval: Duration,
+        capacity: u64,
+        quantum: u64,
+        available_tokens: u64,
+        clock: C,
+    ) -> Self {
+        let start_time = clock.now();
         Self {
             capacity,
-            available_tokens,
-            latest_tick: Instant::now(),
+            available_tokens: (available_tokens * TOKEN_MULTIPLIER) as i64,
+            start_time,
+            latest_tick: 0,
             quantum,
             fill_interval,
+            clock,
+            burst: None,
         }
     }
 
-    fn current_tick(&self) -> f64 {
-        (self.latest_tick.elapsed().as_millis() as f64) / (self.fill_interval.as_millis() as f64)
+    /// rate returns the effective fill rate of the bucket in tokens per second.
+    pub fn rate(&self) -> f64 {
+        self.quantum as f64 / self.fill_interval.as_secs_f64()
     }
 
-    fn adjust_available_tokens(&mut self, tick: f64) {
-        self.latest_tick = Instant::now();
-        if self.available_tokens >= self.capacity {
-            self.available_tokens = self.capacity;
-            return;
-        }
-        self.available_tokens += (tick * self.quantum as f64) as u64;
-        if self.available_tokens >= self.capacity {
-            self.available_tokens = self.capacity;
+    /// with_burst attaches a burst ceiling to the bucket, following the Riot
+    /// style where `burst_pct` is expressed out of 256. It derives a shorter
+    /// `burst_duration = duration * burst_pct` and `burst_limit = ceil(capacity *
+    /// burst_pct)` (both scaled by `1/256`). The ceiling is an auxiliary token
+    /// bucket holding at most `burst_limit` tokens and refilling `burst_limit`
+    /// tokens per `burst_duration`, so no more than `burst_limit` tokens are
+    /// dispensed at once, even when the main bucket is full. Tokens that refill
+    /// during a window can be taken in that same window, so a rolling
+    /// `burst_duration` window is not capped at exactly `burst_limit` tokens.
+    pub fn with_burst(mut self, burst_pct: u64) -> Self
+    where
+        C: Clone,
+    {
+        // The window over which the steady limit applies is the time it takes to
+        // fill the bucket from empty: fill_interval * (capacity / quantum).
+        let duration_nanos = self.fill_interval.as_nanos() * self.capacity as u128 / self.quantum as u128;
+        let burst_limit = (self.capacity * burst_pct).div_ceil(TOKEN_MULTIPLIER).max(1);
+        let burst_duration_nanos = duration_nanos * burst_pct as u128 / TOKEN_MULTIPLIER as u128;
+        let burst_fill_nanos = (burst_duration_nanos / burst_limit as u128).max(1);
+        let burst = Bucket::with_clock(
+            Duration::from_nanos(burst_fill_nanos as u64),
+            burst_limit,
+            1,
+            burst_limit,
+            self.clock.clone(),
+        );
+        self.burst = Some(Box::new(burst));
+        self
+    }
+
+    /// current_tick returns the fractional tick for the given instant, i.e. the
+    /// number of fill intervals elapsed since `start_time`, scaled by
+    /// `TOKEN_MULTIPLIER` so that partially-elapsed intervals are represented.
+    fn current_tick(&self, now: Instant) -> u64 {
+        (now.duration_since(self.start_time).as_nanos() * TOKEN_MULTIPLIER as u128
+            / self.fill_interval.as_nanos()) as u64
+    }
+
+    fn adjust_available_tokens(&mut self, tick: u64) {
+        let capacity = (self.capacity * TOKEN_MULTIPLIER) as i64;
+        if tick > self.latest_tick {
+            // Each fractional tick is worth `quantum / TOKEN_MULTIPLIER` tokens,
+            // i.e. `quantum` fractional-token units. Refills first pay down any
+            // outstanding reservation (negative balance) before accruing spare
+            // capacity, and never exceed the ceiling.
+            self.available_tokens += ((tick - self.latest_tick) * self.quantum) as i64;
+            if self.available_tokens > capacity {
+                self.available_tokens = capacity;
+            }
         }
+        self.latest_tick = tick;
     }
 
     /// TakeAvailable takes up to count immediately available tokens from the bucket. It
@@ -63,15 +246,26 @@ impl Bucket {
         if count == 0 {
             return 0;
         }
-        self.adjust_available_tokens(self.current_tick());
-        if self.available_tokens == 0 {
+        let now = self.clock.now();
+        self.adjust_available_tokens(self.current_tick(now));
+        // Only whole tokens are dispensed; the fractional remainder stays in the
+        // bucket for the next call. A negative balance means nothing is available.
+        let mut available_whole = (self.available_tokens.max(0) / TOKEN_MULTIPLIER as i64) as u64;
+        // The burst ceiling can only lower how much may be dispensed right now.
+        if let Some(burst) = &mut self.burst {
+            available_whole = available_whole.min(burst.available());
+        }
+        if available_whole == 0 {
             return 0;
         }
         let mut tokens = count;
-        if count > self.available_tokens {
-            tokens = self.available_tokens
+        if count > available_whole {
+            tokens = available_whole
+        }
+        self.available_tokens -= (tokens * TOKEN_MULTIPLIER) as i64;
+        if let Some(burst) = &mut self.burst {
+            burst.take_available(tokens);
         }
-        self.available_tokens -= tokens;
         tokens
     }
 
@@ -80,30 +274,111 @@ impl Bucket {
         self.take_available(1)
     }
 
-    // take is the internal version of Take - it takes the current time as
-    // an argument to enable easy testing.
+    /// Available returns the number of whole tokens currently available, without
+    /// removing any of them.
+    pub fn available(&mut self) -> u64 {
+        let now = self.clock.now();
+        self.adjust_available_tokens(self.current_tick(now));
+        let whole = (self.available_tokens.max(0) / TOKEN_MULTIPLIER as i64) as u64;
+        match &mut self.burst {
+            Some(burst) => whole.min(burst.available()),
+            None => whole,
+        }
+    }
+
+    // take is the internal version of Take. When a burst ceiling is configured
+    // it reports the wait needed to satisfy both the steady and the burst
+    // constraints, and only removes tokens from either if both would be
+    // satisfied within max_wait.
     fn take(&mut self, count: u64, max_wait: Duration) -> (Duration, bool) {
         if count == 0 {
             return (ZERO_TIME, true);
         }
-        let tick = self.current_tick();
-        self.adjust_available_tokens(tick);
-        let avail = (self.available_tokens as i64) - (count as i64);
+        let steady_snapshot = (self.available_tokens, self.latest_tick);
+        let (steady_wait, steady_ok) = self.take_steady(count, max_wait);
+        if !steady_ok {
+            self.available_tokens = steady_snapshot.0;
+            self.latest_tick = steady_snapshot.1;
+            return (ZERO_TIME, false);
+        }
+        if let Some(burst) = &mut self.burst {
+            let burst_snapshot = (burst.available_tokens, burst.latest_tick);
+            let (burst_wait, burst_ok) = burst.take_steady(count, max_wait);
+            if !burst_ok {
+                self.available_tokens = steady_snapshot.0;
+                self.latest_tick = steady_snapshot.1;
+                burst.available_tokens = burst_snapshot.0;
+                burst.latest_tick = burst_snapshot.1;
+                return (ZERO_TIME, false);
+            }
+            return (steady_wait.max(burst_wait), true);
+        }
+        (steady_wait, true)
+    }
+
+    // take_steady applies the main token-bucket logic only, ignoring any burst
+    // ceiling. It takes the current time via the clock to enable easy testing.
+    fn take_steady(&mut self, count: u64, max_wait: Duration) -> (Duration, bool) {
+        if count == 0 {
+            return (ZERO_TIME, true);
+        }
+        let now = self.clock.now();
+        let current_tick = self.current_tick(now);
+        self.adjust_available_tokens(current_tick);
+        let needed = (count * TOKEN_MULTIPLIER) as i64;
+        let avail = self.available_tokens - needed;
         if avail >= 0 {
-            self.available_tokens = avail as u64;
+            self.available_tokens = avail;
             return (ZERO_TIME, true);
         }
-        let end_tick = (-avail as f64) / self.quantum as f64;
-        let wait_time = (self.fill_interval.as_millis() as f64) * end_tick;
-        if wait_time > max_wait.as_millis() as f64 {
+        // Round up to the fractional tick at which enough tokens will have
+        // accrued, then convert that tick back into the wait time from now. Each
+        // fractional tick yields `quantum` fractional-token units.
+        let end_tick = current_tick + ((-avail) as u64).div_ceil(self.quantum);
+        let end_time = self.start_time
+            + Duration::from_nanos(
+                self.fill_interval.as_nanos() as u64 * end_tick / TOKEN_MULTIPLIER,
+            );
+        let wait_time = end_time.saturating_duration_since(now);
+        if wait_time > max_wait {
             return (ZERO_TIME, false);
         }
-        (Duration::from_millis(wait_time as u64), true)
+        // Reserve the tokens by driving the balance negative, so that concurrent
+        // waiters each compute a longer wait and the rate stays enforced on the
+        // wait path. Future refills pay the reservation down first.
+        self.available_tokens = avail;
+        (wait_time, true)
+    }
+
+    // reservation_state captures the mutable accounting fields, including those
+    // of any burst sub-bucket, so that a speculative probe can be rolled back
+    // exactly — restoring only the main bucket would leak or double-count burst
+    // tokens.
+    fn reservation_state(&self) -> ReservationState {
+        (
+            self.available_tokens,
+            self.latest_tick,
+            self.burst
+                .as_ref()
+                .map(|b| (b.available_tokens, b.latest_tick)),
+        )
+    }
+
+    // restore_reservation undoes a probe by resetting the fields captured by
+    // [`Bucket::reservation_state`].
+    fn restore_reservation(&mut self, state: ReservationState) {
+        let (tokens, tick, burst) = state;
+        self.available_tokens = tokens;
+        self.latest_tick = tick;
+        if let (Some(b), Some((b_tokens, b_tick))) = (self.burst.as_mut(), burst) {
+            b.available_tokens = b_tokens;
+            b.latest_tick = b_tick;
+        }
     }
 
     /// TakeMaxDuration is take, except that it will only take tokens from the
     /// bucket if the wait time for the tokens is no greater than maxWait.
-
+    ///
     /// If it would take longer than maxWait for the tokens to become available, it does
     /// nothing and reports false, otherwise it returns the time that the caller should
     /// wait until the tokens are actually available, and reports true.
@@ -124,11 +399,274 @@ impl Bucket {
     }
 }
 
+/// `SyncBucket` is a thread-safe handle to a [`Bucket`]. It guards the inner
+/// bucket with a `Mutex` and is cheap to clone: every clone shares the same
+/// underlying state, so the same bucket can be handed to several threads
+/// without each caller having to do its own locking.
+#[derive(Debug, Clone)]
+pub struct SyncBucket {
+    inner: Arc<Mutex<Bucket>>,
+}
+
+impl SyncBucket {
+    pub fn new(
+        fill_interval: Duration,
+        capacity: u64,
+        quantum: u64,
+        available_tokens: u64,
+    ) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(Bucket::new(
+                fill_interval,
+                capacity,
+                quantum,
+                available_tokens,
+            ))),
+        }
+    }
+
+    /// TakeAvailable takes up to count immediately available tokens from the bucket.
+    /// It returns the number of tokens removed, or zero if there are no available
+    /// tokens. It does not block.
+    pub fn take_available(&self, count: u64) -> u64 {
+        self.inner.lock().unwrap().take_available(count)
+    }
+
+    /// TakeMaxDuration is like [`Bucket::take_max_duration`], shared across threads.
+    pub fn take_max_duration(&self, count: u64, max_wait: Duration) -> (Duration, bool) {
+        self.inner.lock().unwrap().take_max_duration(count, max_wait)
+    }
+
+    /// WaitMaxDuration is like [`Bucket::wait_max_duration`], shared across threads.
+    /// The sleep happens outside the lock so that other threads may keep taking
+    /// tokens while this caller waits.
+    pub fn wait_max_duration(&self, count: u64, max_wait: Duration) -> bool {
+        let (sleep_time, ok) = self.inner.lock().unwrap().take(count, max_wait);
+        if sleep_time.as_millis() > 0 {
+            thread::sleep(sleep_time);
+        }
+        ok
+    }
+}
+
+/// `MultiBucket` enforces several token buckets at once: a request is only
+/// granted when every constituent bucket has the tokens available. This models
+/// real API quotas that stack a short-window burst limit on top of a
+/// long-window total, which a single [`Bucket`] cannot express.
+#[derive(Debug)]
+pub struct MultiBucket {
+    buckets: Vec<Bucket>,
+}
+
+impl MultiBucket {
+    /// new builds a composite limiter from a set of buckets.
+    pub fn new(buckets: Vec<Bucket>) -> Self {
+        Self { buckets }
+    }
+
+    /// parse reads the Riot-style string form `"count:seconds,count:seconds"`,
+    /// turning each `count:seconds` pair into one [`Bucket`] that refills `count`
+    /// tokens over `seconds` seconds (e.g. `"20:1,100:120"` enforces 20 per
+    /// second AND 100 per two minutes).
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let mut buckets = Vec::new();
+        for pair in spec.split(',') {
+            let pair = pair.trim();
+            if pair.is_empty() {
+                continue;
+            }
+            let (count, seconds) = pair
+                .split_once(':')
+                .ok_or_else(|| format!("invalid limit pair {:?}, expected count:seconds", pair))?;
+            let count: u64 = count
+                .trim()
+                .parse()
+                .map_err(|_| format!("invalid count in {:?}", pair))?;
+            let seconds: u64 = seconds
+                .trim()
+                .parse()
+                .map_err(|_| format!("invalid seconds in {:?}", pair))?;
+            if count == 0 || seconds == 0 {
+                return Err(format!("count and seconds must be positive in {:?}", pair));
+            }
+            let interval_nanos = Duration::from_secs(seconds).as_nanos() / count as u128;
+            let fill_interval = Duration::new(
+                (interval_nanos / 1_000_000_000) as u64,
+                (interval_nanos % 1_000_000_000) as u32,
+            );
+            // A rate above one token per nanosecond rounds the fill interval down
+            // to zero, which would divide by zero in `current_tick`; reject it.
+            if fill_interval.is_zero() {
+                return Err(format!("rate too high to represent in {:?}", pair));
+            }
+            buckets.push(Bucket::new(fill_interval, count, 1, count));
+        }
+        if buckets.is_empty() {
+            return Err("no limit pairs found".to_string());
+        }
+        Ok(Self::new(buckets))
+    }
+
+    /// TakeAvailable takes up to count tokens that are immediately available from
+    /// every bucket, returning the number removed — the most that all buckets can
+    /// satisfy at once.
+    pub fn take_available(&mut self, count: u64) -> u64 {
+        let grant = self
+            .buckets
+            .iter_mut()
+            .map(|b| b.available())
+            .min()
+            .unwrap_or(0)
+            .min(count);
+        if grant > 0 {
+            for bucket in &mut self.buckets {
+                bucket.take_available(grant);
+            }
+        }
+        grant
+    }
+
+    /// TakeMaxDuration reports the longest wait across all buckets and reserves
+    /// `count` tokens from every bucket, all-or-nothing. If every bucket can
+    /// satisfy the request within maxWait it debits them all — even on the wait
+    /// path, so that concurrent callers stay staggered and the rate holds — and
+    /// returns the time to wait with true. If any bucket would take longer than
+    /// maxWait it debits nothing and reports false.
+    pub fn take_max_duration(&mut self, count: u64, max_wait: Duration) -> (Duration, bool) {
+        // Probe every bucket without committing, restoring state afterwards, so
+        // that a partial deduction can never leak out when some buckets must wait.
+        let snapshot: Vec<ReservationState> =
+            self.buckets.iter().map(|b| b.reservation_state()).collect();
+        let mut wait = ZERO_TIME;
+        let mut all_ok = true;
+        for bucket in &mut self.buckets {
+            let (d, ok) = bucket.take_max_duration(count, max_wait);
+            if !ok {
+                all_ok = false;
+                break;
+            }
+            if d > wait {
+                wait = d;
+            }
+        }
+        // Always undo the probe's effects before deciding what to commit.
+        for (bucket, state) in self.buckets.iter_mut().zip(snapshot.iter().copied()) {
+            bucket.restore_reservation(state);
+        }
+        if !all_ok {
+            return (ZERO_TIME, false);
+        }
+        // Reserve count from every bucket, whether the tokens are immediately
+        // available or must be waited for. The probe proved each bucket is within
+        // max_wait; if the clock slipped past that boundary in between, roll the
+        // whole thing back and report false rather than leave a partial reservation.
+        for i in 0..self.buckets.len() {
+            let (_, ok) = self.buckets[i].take_max_duration(count, max_wait);
+            if !ok {
+                for (bucket, state) in self.buckets.iter_mut().zip(snapshot) {
+                    bucket.restore_reservation(state);
+                }
+                return (ZERO_TIME, false);
+            }
+        }
+        (wait, true)
+    }
+}
+
+/// `AsyncBucket` is a thread-safe, `tokio`-based handle to a [`Bucket`] whose
+/// `take` operations yield to the runtime instead of parking the OS thread,
+/// making the limiter usable inside async request pipelines.
+#[cfg(feature = "tokio")]
+#[derive(Debug, Clone)]
+pub struct AsyncBucket {
+    inner: Arc<Mutex<Bucket>>,
+}
+
+#[cfg(feature = "tokio")]
+impl AsyncBucket {
+    pub fn new(
+        fill_interval: Duration,
+        capacity: u64,
+        quantum: u64,
+        available_tokens: u64,
+    ) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(Bucket::new(
+                fill_interval,
+                capacity,
+                quantum,
+                available_tokens,
+            ))),
+        }
+    }
+
+    /// take consumes a single token, sleeping asynchronously until one is
+    /// available, and returns the number of tokens remaining after the take.
+    pub async fn take(&self) -> u64 {
+        self.take_n(1).await
+    }
+
+    /// take_n consumes `count` tokens, awaiting the refill wait when the bucket
+    /// is empty rather than blocking the thread, and returns the number of
+    /// tokens remaining after the take. The tokens are reserved up front (so
+    /// concurrent takers do not all wait for the same token), then the computed
+    /// wait is awaited before returning.
+    pub async fn take_n(&self, count: u64) -> u64 {
+        let (wait, remaining) = {
+            let mut bucket = self.inner.lock().unwrap();
+            let (wait, _) = bucket.take(count, Duration::MAX);
+            // Read the remainder under the same lock as the take so the returned
+            // count reflects this take rather than a racy post-wait snapshot that
+            // other tasks and refills may have mutated during the await.
+            (wait, bucket.available())
+        };
+        // Lock released before sleeping so other tasks may keep taking.
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+        }
+        remaining
+    }
+
+    /// wait_max_duration is the async analogue of [`Bucket::wait_max_duration`]:
+    /// it reserves the tokens and awaits only if the required wait is within
+    /// `max_wait`, reporting whether the tokens were taken.
+    pub async fn wait_max_duration(&self, count: u64, max_wait: Duration) -> bool {
+        let wait = {
+            let mut bucket = self.inner.lock().unwrap();
+            let (wait, ok) = bucket.take(count, max_wait);
+            if !ok {
+                return false;
+            }
+            wait
+        };
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+        }
+        true
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::Bucket;
+    use crate::{Bucket, ManualClock, MultiBucket};
+    use std::sync::Arc;
     use std::thread;
     use std::time::Duration;
+
+    #[test]
+    fn take_available_with_manual_clock() {
+        let clock = Arc::new(ManualClock::new());
+        let mut bucket =
+            Bucket::with_clock(Duration::from_secs(3), 100, 100, 100, Arc::clone(&clock));
+        assert_eq!(bucket.take_available(200), 100);
+        assert_eq!(bucket.take_available(100), 0);
+        clock.advance(Duration::from_secs(3));
+        assert_eq!(bucket.take_available(100), 100);
+        clock.advance(Duration::from_secs(2));
+        assert_eq!(bucket.take_available(100), 66);
+        clock.advance(Duration::from_secs(3));
+        assert_eq!(bucket.take_available(200), 100);
+    }
     #[test]
     fn take_avaliable_works() {
         let mut bucket = Bucket::new(Duration::from_secs(3), 100, 100, 100);
@@ -140,6 +678,8 @@ mod tests {
         let count = bucket.take_available(100);
         assert_eq!(count, 100);
         thread::sleep(Duration::from_secs(2));
+        // Two of the three seconds in a fill interval have elapsed, so roughly
+        // two thirds of a quantum has accrued at fractional resolution.
         let count = bucket.take_available(100);
         assert_eq!(66, count);
         thread::sleep(Duration::from_secs(3));
@@ -147,19 +687,134 @@ mod tests {
         assert_eq!(100, count);
     }
 
+    #[test]
+    fn multi_bucket_parse_enforces_every_limit() {
+        // 20 per second AND 100 per 120 seconds.
+        let mut limiter = MultiBucket::parse("20:1,100:120").unwrap();
+        // The 20/s bucket is the binding constraint at t=0.
+        assert_eq!(limiter.take_available(50), 20);
+    }
+
+    #[test]
+    fn wait_path_reserves_tokens() {
+        let clock = Arc::new(ManualClock::new());
+        // 1 token per second, starting full with 10 tokens.
+        let mut bucket =
+            Bucket::with_clock(Duration::from_secs(1), 10, 1, 10, Arc::clone(&clock));
+        assert_eq!(bucket.take_available(10), 10);
+        // Two successive waiters must get staggered waits: the first reserves the
+        // next token, so the second has to wait a further second.
+        let (first, ok) = bucket.take_max_duration(1, Duration::from_secs(10));
+        assert!(ok);
+        assert_eq!(first.as_secs(), 1);
+        let (second, ok) = bucket.take_max_duration(1, Duration::from_secs(10));
+        assert!(ok);
+        assert_eq!(second.as_secs(), 2);
+        // The reservations are reflected in the (negative) balance.
+        assert_eq!(bucket.available_tokens, -2 * super::TOKEN_MULTIPLIER as i64);
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test(start_paused = true)]
+    async fn async_take_n_yields_and_reports_remaining() {
+        use crate::AsyncBucket;
+        // 10 tokens, refilling one per second.
+        let bucket = AsyncBucket::new(Duration::from_secs(1), 10, 1, 10);
+        // Draining all ten is immediate and leaves nothing behind.
+        assert_eq!(bucket.take_n(10).await, 0);
+        // The next take has to await a refill; with paused time tokio advances
+        // the virtual clock automatically instead of blocking.
+        assert_eq!(bucket.take().await, 0);
+    }
+
+    #[test]
+    fn with_rate_realizes_requested_rate() {
+        // A modest rate a single-token bucket can represent directly.
+        let bucket = Bucket::with_rate(100.0, 100).unwrap();
+        assert!((bucket.rate() - 100.0).abs() <= 100.0 * 0.01);
+        // A rate of a million tokens per second: a 1µs fill interval represents
+        // it exactly, so the search need not go past a quantum of 1.
+        let fast = Bucket::with_rate(1_000_000.0, 1000).unwrap();
+        assert!((fast.rate() - 1_000_000.0).abs() <= 1_000_000.0 * 0.01);
+        assert!(fast.quantum >= 1);
+    }
+
+    #[test]
+    fn with_rate_rejects_non_positive_rate() {
+        assert!(Bucket::with_rate(0.0, 100).is_err());
+        assert!(Bucket::with_rate(-1.0, 100).is_err());
+        assert!(Bucket::with_rate(f64::NAN, 100).is_err());
+        assert!(Bucket::with_rate(f64::INFINITY, 100).is_err());
+    }
+
+    #[test]
+    fn multi_bucket_parse_derives_fill_intervals() {
+        let limiter = MultiBucket::parse("20:1,100:120").unwrap();
+        assert_eq!(limiter.buckets.len(), 2);
+        // 20 tokens over 1s -> one token every 50ms.
+        assert_eq!(limiter.buckets[0].fill_interval, Duration::from_millis(50));
+        assert_eq!(limiter.buckets[0].capacity, 20);
+        // 100 tokens over 120s -> one token every 1.2s.
+        assert_eq!(limiter.buckets[1].fill_interval, Duration::from_millis(1200));
+        assert_eq!(limiter.buckets[1].capacity, 100);
+    }
+
+    #[test]
+    fn multi_bucket_parse_rejects_bad_input() {
+        assert!(MultiBucket::parse("nope").is_err());
+        assert!(MultiBucket::parse("20").is_err());
+        assert!(MultiBucket::parse("x:1").is_err());
+        assert!(MultiBucket::parse("20:y").is_err());
+        assert!(MultiBucket::parse("0:1").is_err());
+        assert!(MultiBucket::parse("20:0").is_err());
+        assert!(MultiBucket::parse("").is_err());
+        // A rate above one token per nanosecond cannot be represented.
+        assert!(MultiBucket::parse("2000000000:1").is_err());
+    }
+
+    #[test]
+    fn burst_ceiling_caps_immediate_grant() {
+        let clock = Arc::new(ManualClock::new());
+        // 100 tokens / second, with a 50% (128/256) burst factor capping any
+        // immediate grant to 50 tokens.
+        let mut bucket = Bucket::with_clock(Duration::from_millis(10), 100, 1, 100, Arc::clone(&clock))
+            .with_burst(128);
+        // Even though the main bucket is full, the burst ceiling limits us to 50.
+        assert_eq!(bucket.take_available(100), 50);
+    }
+
     #[test]
     fn take_max_duration_works() {
-        let mut bucket = Bucket::new(Duration::from_secs(3), 100, 100, 100);
-        bucket.take_available(100);
-        let (time, ok) = bucket.take_max_duration(100, Duration::from_secs(4));
+        // Fresh empty bucket per scenario: because the wait path now reserves
+        // tokens, repeated calls on one bucket would accumulate waits, so each
+        // case below starts from a clean state.
+        let empty = || {
+            let mut bucket = Bucket::with_clock(
+                Duration::from_secs(3),
+                100,
+                100,
+                100,
+                Arc::new(ManualClock::new()),
+            );
+            bucket.take_available(100);
+            bucket
+        };
+
+        let (time, ok) = empty().take_max_duration(100, Duration::from_secs(4));
         assert_eq!(time.as_millis(), 3000);
-        assert_eq!(ok, true);
-        let (time, ok) = bucket.take_max_duration(100, Duration::from_secs(1));
+        assert!(ok);
+
+        let (time, ok) = empty().take_max_duration(100, Duration::from_secs(1));
         assert_eq!(time.as_millis(), 0);
-        assert_eq!(ok, false);
-        thread::sleep(Duration::from_secs(1));
+        assert!(!ok);
+
+        let clock = Arc::new(ManualClock::new());
+        let mut bucket =
+            Bucket::with_clock(Duration::from_secs(3), 100, 100, 100, Arc::clone(&clock));
+        bucket.take_available(100);
+        clock.advance(Duration::from_secs(1));
         let (time, ok) = bucket.take_max_duration(100, Duration::from_secs(7));
         assert_eq!(time.as_secs(), 2);
-        assert_eq!(ok, true);
+        assert!(ok);
     }
 }